@@ -1,7 +1,10 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 // WAVファイルのヘッダー構造体
 #[derive(Debug)]
@@ -40,9 +43,39 @@ enum MidiEvent {
         program: u8,
         time: u32,
     },
+    SetTempo {
+        microseconds_per_quarter: u32,
+        time: u32,
+    },
+    PitchBend {
+        channel: u8,
+        value: u16,
+        time: u32,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+        time: u32,
+    },
     Unknown,
 }
 
+impl MidiEvent {
+    // イベントの直前のデルタタイムを取得（Unknownは0）
+    fn time(&self) -> u32 {
+        match self {
+            MidiEvent::NoteOn { time, .. }
+            | MidiEvent::NoteOff { time, .. }
+            | MidiEvent::ProgramChange { time, .. }
+            | MidiEvent::SetTempo { time, .. }
+            | MidiEvent::PitchBend { time, .. }
+            | MidiEvent::ControlChange { time, .. } => *time,
+            MidiEvent::Unknown => 0,
+        }
+    }
+}
+
 // MIDIトラック
 #[derive(Debug)]
 struct MidiTrack {
@@ -126,6 +159,13 @@ fn read_be_u32(data: &[u8], pos: &mut usize) -> u32 {
     result
 }
 
+// リトルエンディアンの16ビット読み込み（MUSヘッダー用）
+fn read_le_u16(data: &[u8], pos: &mut usize) -> u16 {
+    let result = u16::from_le_bytes([data[*pos], data[*pos + 1]]);
+    *pos += 2;
+    result
+}
+
 // MIDIヘッダーチャンクの読み込み
 fn read_midi_header(
     data: &[u8],
@@ -146,6 +186,12 @@ fn read_midi_header(
     let track_count = read_be_u16(data, pos);
     let ticks_per_quarter = read_be_u16(data, pos);
 
+    // divisionが0だと「1四分音符あたりのティック数」が存在せず、ticks_to_seconds_with_mapの
+    // 除算がNaNを生む（メトリカル方式の範囲外で、SMPTE方式の解釈でも0は無効）
+    if ticks_per_quarter == 0 {
+        return Err("Invalid MIDI header: ticks_per_quarter must not be 0".into());
+    }
+
     Ok((format, track_count, ticks_per_quarter))
 }
 
@@ -222,6 +268,36 @@ fn parse_midi_event(
                 time: delta_time,
             })
         }
+        0xE0 => {
+            // Pitch Bend: 14ビット値（lsb, msbの順）、8192がセンター
+            if *pos + 1 >= data.len() {
+                return Ok(MidiEvent::Unknown);
+            }
+            let lsb = data[*pos];
+            let msb = data[*pos + 1];
+            *pos += 2;
+            let value = ((msb as u16) << 7) | lsb as u16;
+            Ok(MidiEvent::PitchBend {
+                channel,
+                value,
+                time: delta_time,
+            })
+        }
+        0xB0 => {
+            // Control Change
+            if *pos + 1 >= data.len() {
+                return Ok(MidiEvent::Unknown);
+            }
+            let controller = data[*pos];
+            let value = data[*pos + 1];
+            *pos += 2;
+            Ok(MidiEvent::ControlChange {
+                channel,
+                controller,
+                value,
+                time: delta_time,
+            })
+        }
         _ => {
             // その他のイベントはスキップ
             if status == 0xFF {
@@ -229,8 +305,20 @@ fn parse_midi_event(
                 if *pos >= data.len() {
                     return Ok(MidiEvent::Unknown);
                 }
+                let meta_type = data[*pos];
                 *pos += 1; // event type
                 if let Ok(length) = read_vlq(data, pos) {
+                    if meta_type == 0x51 && length == 3 && *pos + 3 <= data.len() {
+                        // Set Tempo: 3バイトのビッグエンディアンでµs/四分音符
+                        let microseconds_per_quarter = ((data[*pos] as u32) << 16)
+                            | ((data[*pos + 1] as u32) << 8)
+                            | data[*pos + 2] as u32;
+                        *pos += 3;
+                        return Ok(MidiEvent::SetTempo {
+                            microseconds_per_quarter,
+                            time: delta_time,
+                        });
+                    }
                     *pos += length as usize;
                 }
             } else if status >= 0x80 {
@@ -301,34 +389,286 @@ fn read_midi_file<P: AsRef<Path>>(filename: P) -> Result<MidiFile, Box<dyn std::
     })
 }
 
+// MUSのチャンネル番号をMIDIチャンネル番号へ変換する。DMXの慣習でチャンネル9と15を入れ替え、
+// MUSのパーカッションチャンネル(15)をGM準拠のドラムチャンネル(9)に合わせる。
+fn mus_channel_to_midi_channel(mus_channel: u8) -> u8 {
+    match mus_channel {
+        9 => 15,
+        15 => 9,
+        other => other,
+    }
+}
+
+// MUSのコントローラ番号をGM準拠のMIDI CC番号に変換する
+fn mus_controller_to_midi_cc(mus_controller: u8) -> u8 {
+    match mus_controller {
+        1 => 0,  // Bank select
+        2 => 1,  // Modulation
+        3 => 7,  // Volume
+        4 => 10, // Pan
+        5 => 11, // Expression
+        6 => 91, // Reverb depth
+        7 => 93, // Chorus depth
+        8 => 64, // Sustain pedal
+        9 => 67, // Soft pedal
+        other => other,
+    }
+}
+
+// MUSのシステムイベント番号（10-14）をGM準拠のMIDI CC番号に変換する
+fn mus_system_event_to_midi_cc(mus_controller: u8) -> u8 {
+    match mus_controller {
+        10 => 120, // All Sounds Off
+        11 => 123, // All Notes Off
+        12 => 126, // Mono Mode On
+        13 => 127, // Poly Mode On
+        14 => 121, // Reset All Controllers
+        other => other,
+    }
+}
+
+// MUSファイル（Doomなどで使われるMIDI風の簡易フォーマット）を読み込み、
+// 単一トラックのMidiFileとして既存のMidiEventにマッピングする
+fn read_mus_file<P: AsRef<Path>>(filename: P) -> Result<MidiFile, Box<dyn std::error::Error>> {
+    let mut file = File::open(filename)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    if buffer.len() < 6 || &buffer[0..4] != b"MUS\x1a" {
+        return Err("Invalid MUS header".into());
+    }
+
+    let mut pos = 4;
+    let score_length = read_le_u16(&buffer, &mut pos);
+    let score_start = read_le_u16(&buffer, &mut pos);
+
+    let mut pos = score_start as usize;
+    let end_position = if score_length == 0 {
+        buffer.len()
+    } else {
+        std::cmp::min(pos + score_length as usize, buffer.len())
+    };
+
+    let mut events = Vec::new();
+    let mut last_volume: HashMap<u8, u8> = HashMap::new();
+    let mut pending_delay = 0u32; // 直前のイベント群の後に読んだ遅延。次のイベントのtimeになる
+
+    while pos < end_position {
+        let descriptor = buffer[pos];
+        pos += 1;
+        let is_last = (descriptor & 0x80) != 0;
+        let event_type = (descriptor >> 4) & 0x07;
+        let channel = mus_channel_to_midi_channel(descriptor & 0x0F);
+
+        let time = pending_delay;
+        pending_delay = 0;
+
+        match event_type {
+            0 => {
+                // Release Note
+                if pos >= buffer.len() {
+                    break;
+                }
+                let note = buffer[pos] & 0x7F;
+                pos += 1;
+                events.push(MidiEvent::NoteOff { channel, note, time });
+            }
+            1 => {
+                // Play Note（高位ビットが立っていればvolumeバイトが続く。無ければ前回値、
+                // それも無ければ100をデフォルトとする）
+                if pos >= buffer.len() {
+                    break;
+                }
+                let note_byte = buffer[pos];
+                pos += 1;
+                let note = note_byte & 0x7F;
+                let velocity = if note_byte & 0x80 != 0 {
+                    if pos >= buffer.len() {
+                        break;
+                    }
+                    let volume = buffer[pos] & 0x7F;
+                    pos += 1;
+                    last_volume.insert(channel, volume);
+                    volume
+                } else {
+                    *last_volume.get(&channel).unwrap_or(&100)
+                };
+                events.push(MidiEvent::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                    time,
+                });
+            }
+            2 => {
+                // Pitch Bend: 0..255（128がセンター）をMIDIの0..16383相当へ拡大
+                if pos >= buffer.len() {
+                    break;
+                }
+                let value = (buffer[pos] as u16) * 64;
+                pos += 1;
+                events.push(MidiEvent::PitchBend {
+                    channel,
+                    value,
+                    time,
+                });
+            }
+            3 => {
+                // System Event
+                if pos >= buffer.len() {
+                    break;
+                }
+                let controller = buffer[pos];
+                pos += 1;
+                events.push(MidiEvent::ControlChange {
+                    channel,
+                    controller: mus_system_event_to_midi_cc(controller),
+                    value: 0,
+                    time,
+                });
+            }
+            4 => {
+                // Controller Change（0番はProgram Change）
+                if pos + 1 >= buffer.len() {
+                    break;
+                }
+                let controller = buffer[pos];
+                let value = buffer[pos + 1];
+                pos += 2;
+                if controller == 0 {
+                    events.push(MidiEvent::ProgramChange {
+                        channel,
+                        program: value,
+                        time,
+                    });
+                } else {
+                    events.push(MidiEvent::ControlChange {
+                        channel,
+                        controller: mus_controller_to_midi_cc(controller),
+                        value,
+                        time,
+                    });
+                }
+            }
+            6 => {
+                // Score End
+                break;
+            }
+            _ => {}
+        }
+
+        if is_last {
+            match read_vlq(&buffer, &mut pos) {
+                Ok(delay) => pending_delay = delay,
+                Err(_) => break,
+            }
+        }
+    }
+
+    Ok(MidiFile {
+        format: 0,
+        track_count: 1,
+        // MUSはテンポの概念を持たず、DMXエンジンが固定140Hzでイベントを処理する。
+        // ticks_per_quarterを140とし、呼び出し側で1拍=1秒（60BPM）として描画することで
+        // この固定レートを既存のテンポベースの変換処理にそのまま乗せる。
+        ticks_per_quarter: 140,
+        tracks: vec![MidiTrack { events }],
+    })
+}
+
+// テンポマップ（絶対ティック, µs/四分音符）をテンポ変更イベントから構築
+fn build_tempo_map(midi_file: &MidiFile) -> Vec<(u32, u32)> {
+    let mut tempo_map = Vec::new();
+
+    for track in &midi_file.tracks {
+        let mut current_tick = 0u32;
+        for event in &track.events {
+            current_tick += event.time();
+            if let MidiEvent::SetTempo {
+                microseconds_per_quarter,
+                ..
+            } = event
+            {
+                tempo_map.push((current_tick, *microseconds_per_quarter));
+            }
+        }
+    }
+
+    tempo_map.sort_by_key(|&(tick, _)| tick);
+    tempo_map
+}
+
+// テンポマップに従い絶対ティックを秒数に変換（区間ごとに積算）
+fn ticks_to_seconds_with_map(
+    tick: u32,
+    tempo_map: &[(u32, u32)],
+    ticks_per_quarter: u16,
+    default_microseconds_per_quarter: u32,
+) -> f64 {
+    let mut seconds = 0.0;
+    let mut last_tick = 0u32;
+    let mut current_usec_per_quarter = if tempo_map.is_empty() {
+        default_microseconds_per_quarter
+    } else {
+        500_000 // 最初のテンポイベントまでは120BPM（デフォルト）
+    };
+
+    for &(event_tick, usec_per_quarter) in tempo_map {
+        if event_tick >= tick {
+            break;
+        }
+        let delta_ticks = event_tick - last_tick;
+        seconds += delta_ticks as f64 / ticks_per_quarter as f64
+            * (current_usec_per_quarter as f64 / 1_000_000.0);
+        last_tick = event_tick;
+        current_usec_per_quarter = usec_per_quarter;
+    }
+
+    let delta_ticks = tick - last_tick;
+    seconds +=
+        delta_ticks as f64 / ticks_per_quarter as f64 * (current_usec_per_quarter as f64 / 1_000_000.0);
+    seconds
+}
+
 // MIDIイベントから音符リストに変換
-fn events_to_notes(midi_file: &MidiFile, tempo: f64) -> Vec<Note> {
-    let ticks_to_seconds =
-        |tick: u32| -> f64 { tick as f64 / midi_file.ticks_per_quarter as f64 * 60.0 / tempo };
+// チャンネルごとのピッチベンド履歴（絶対時刻[秒], 14bit値）。時刻昇順。
+type PitchBendMap = HashMap<u8, Vec<(f64, u16)>>;
+// チャンネルごとの最後に受信したパン位置（CC10, 0..127）
+type PanMap = HashMap<u8, u8>;
+
+fn events_to_notes(midi_file: &MidiFile, tempo: f64) -> (Vec<Note>, PitchBendMap, PanMap) {
+    let tempo_map = build_tempo_map(midi_file);
+    // テンポイベントが一つも無い場合のみ、CLIのtempo引数を採用する
+    let default_microseconds_per_quarter = (60_000_000.0 / tempo) as u32;
+    let ticks_to_seconds = |tick: u32| -> f64 {
+        ticks_to_seconds_with_map(
+            tick,
+            &tempo_map,
+            midi_file.ticks_per_quarter,
+            default_microseconds_per_quarter,
+        )
+    };
 
     let mut note_on_events: HashMap<(u8, u8), (u8, f64)> = HashMap::new(); // (channel, note) -> (velocity, start_time)
     let mut notes = Vec::new();
+    let mut pitch_bends: PitchBendMap = HashMap::new();
+    let mut pans: PanMap = HashMap::new();
 
     for track in &midi_file.tracks {
         let mut current_tick = 0u32;
         for event in &track.events {
+            current_tick += event.time();
             match event {
                 MidiEvent::NoteOn {
                     channel,
                     note,
                     velocity,
-                    time,
+                    ..
                 } => {
-                    current_tick += time;
                     let start_time = ticks_to_seconds(current_tick);
                     note_on_events.insert((*channel, *note), (*velocity, start_time));
                 }
-                MidiEvent::NoteOff {
-                    channel,
-                    note,
-                    time,
-                } => {
-                    current_tick += time;
+                MidiEvent::NoteOff { channel, note, .. } => {
                     let end_time = ticks_to_seconds(current_tick);
                     if let Some((velocity, start_time)) = note_on_events.remove(&(*channel, *note))
                     {
@@ -344,12 +684,28 @@ fn events_to_notes(midi_file: &MidiFile, tempo: f64) -> Vec<Note> {
                         }
                     }
                 }
+                MidiEvent::PitchBend { channel, value, .. } => {
+                    let time = ticks_to_seconds(current_tick);
+                    pitch_bends.entry(*channel).or_default().push((time, *value));
+                }
+                MidiEvent::ControlChange {
+                    channel,
+                    controller: 10,
+                    value,
+                    ..
+                } => {
+                    pans.insert(*channel, *value);
+                }
                 _ => {}
             }
         }
     }
 
-    notes
+    for bends in pitch_bends.values_mut() {
+        bends.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    (notes, pitch_bends, pans)
 }
 
 // 波形生成関数
@@ -407,28 +763,391 @@ fn generate_noise(is_short: bool, sample_rate: u32, duration: f64) -> Vec<f64> {
         .collect()
 }
 
-// 音符を波形に変換
-fn note_to_waveform(note: &Note, sample_rate: u32, total_duration: f64) -> Vec<f64> {
+// ADSRエンベロープ（秒単位のattack/decay/release, 0..1のsustainレベル）
+#[derive(Debug, Clone, Copy)]
+struct Envelope {
+    attack: f64,
+    decay: f64,
+    sustain: f64,
+    release: f64,
+}
+
+impl Envelope {
+    // ノイズ（ドラム）用: 鋭い立ち上がりと短いリリース
+    fn noise_default() -> Self {
+        Envelope {
+            attack: 0.001,
+            decay: 0.02,
+            sustain: 0.6,
+            release: 0.03,
+        }
+    }
+
+    // 矩形波（メロディ/ハーモニー）用
+    fn pulse_default() -> Self {
+        Envelope {
+            attack: 0.005,
+            decay: 0.05,
+            sustain: 0.8,
+            release: 0.08,
+        }
+    }
+
+    // 三角波（ベース）用: 長めのリリースで余韻を残す
+    fn triangle_default() -> Self {
+        Envelope {
+            attack: 0.01,
+            decay: 0.05,
+            sustain: 0.85,
+            release: 0.25,
+        }
+    }
+
+    // attack/decay/sustain区間のみでの振幅倍率（ゲートやリリースは考慮しない）
+    fn attack_decay_level(&self, elapsed: f64) -> f64 {
+        if elapsed < self.attack {
+            if self.attack > 0.0 { elapsed / self.attack } else { 1.0 }
+        } else if elapsed < self.attack + self.decay {
+            let decay_progress = if self.decay > 0.0 {
+                (elapsed - self.attack) / self.decay
+            } else {
+                1.0
+            };
+            1.0 - decay_progress * (1.0 - self.sustain)
+        } else {
+            self.sustain
+        }
+    }
+
+    // ノートオンからの経過時間tにおける振幅倍率（0..1）を求める。
+    // gate_duration（note.duration）を超えるとリリース区間に入る。gate_durationがattack+decayより
+    // 短い場合、decayが完了する前にゲートが閉じることがあるため、リリースは「そのときの実際の振幅」
+    // から始める（sustainに到達済みだと決め打ちしない）。そうしないと1サンプルで振幅が飛ぶクリックが出る。
+    fn amplitude_at(&self, t: f64, gate_duration: f64) -> f64 {
+        if t < gate_duration {
+            self.attack_decay_level(t)
+        } else if t < gate_duration + self.release {
+            let level_at_gate_close = self.attack_decay_level(gate_duration);
+            let release_progress = if self.release > 0.0 {
+                (t - gate_duration) / self.release
+            } else {
+                1.0
+            };
+            level_at_gate_close * (1.0 - release_progress)
+        } else {
+            0.0
+        }
+    }
+}
+
+// デフォルトのピッチベンドレンジ（半音単位）。フルベンドでこの幅だけ上下する。
+const DEFAULT_BEND_RANGE_SEMITONES: f64 = 2.0;
+
+// 14bitのピッチベンド値（8192がセンター）をセント単位のずれに変換
+fn pitch_bend_cents(value: u16, bend_range_semitones: f64) -> f64 {
+    (value as f64 - 8192.0) / 8192.0 * bend_range_semitones * 100.0
+}
+
+// 指定した絶対時刻で有効なピッチベンド値を履歴から求める（無ければセンター）
+fn bend_value_at(channel_bends: &[(f64, u16)], time: f64) -> u16 {
+    let mut value = 8192u16;
+    for &(event_time, bend_value) in channel_bends {
+        if event_time > time {
+            break;
+        }
+        value = bend_value;
+    }
+    value
+}
+
+// ピッチベンドを反映しつつ位相をサンプルごとに積算して矩形波/三角波を生成する
+fn generate_pitched_wave<F: Fn(f64) -> f64>(
+    base_frequency: f64,
+    note_start_time: f64,
+    channel_bends: &[(f64, u16)],
+    bend_range_semitones: f64,
+    sample_rate: u32,
+    duration: f64,
+    shape: F,
+) -> Vec<f64> {
+    let samples = (sample_rate as f64 * duration) as usize;
+    let mut phase = 0.0f64;
+
+    (0..samples)
+        .map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            let cents = pitch_bend_cents(
+                bend_value_at(channel_bends, note_start_time + t),
+                bend_range_semitones,
+            );
+            let frequency = base_frequency * 2f64.powf(cents / 1200.0);
+            let sample = shape(phase);
+            phase = (phase + frequency / sample_rate as f64) % 1.0;
+            sample
+        })
+        .collect()
+}
+
+// NES APUが実際に持つ5つの発音チャンネル
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Voice {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+impl Voice {
+    fn all() -> [Voice; 5] {
+        [
+            Voice::Pulse1,
+            Voice::Pulse2,
+            Voice::Triangle,
+            Voice::Noise,
+            Voice::Dmc,
+        ]
+    }
+
+    fn envelope(&self) -> Envelope {
+        match self {
+            Voice::Pulse1 | Voice::Pulse2 => Envelope::pulse_default(),
+            Voice::Triangle => Envelope::triangle_default(),
+            Voice::Noise | Voice::Dmc => Envelope::noise_default(),
+        }
+    }
+
+    // デフォルトのパン位置（0.0=左 .. 0.5=中央 .. 1.0=右）
+    fn default_pan(&self) -> f64 {
+        match self {
+            Voice::Pulse1 => 0.35,
+            Voice::Pulse2 => 0.65,
+            Voice::Triangle | Voice::Noise | Voice::Dmc => 0.5,
+        }
+    }
+}
+
+// 実効パン位置を求める。CC10の指定があればそれを優先し、無ければボイスごとの既定値を使う。
+fn effective_pan(voice: Voice, channel: u8, pans: &PanMap) -> f64 {
+    match pans.get(&channel) {
+        Some(&cc_value) => cc_value as f64 / 127.0,
+        None => voice.default_pan(),
+    }
+}
+
+// MIDIチャンネル10（0始まりで9）はGM準拠のドラムチャンネル
+const DRUM_CHANNEL: u8 = 9;
+
+// ドラムチャンネルのうち低音のバスドラム系をDMC、それ以外をNoiseへ振り分ける
+fn is_bass_drum_key(midi_note: u8) -> bool {
+    matches!(midi_note, 35 | 36)
+}
+
+// notesのうちインデックスnote_indexが、どのボイスの何秒から何秒まで鳴るかを表す
+#[derive(Debug, Clone, Copy)]
+struct VoiceAssignment {
+    note_index: usize,
+    voice: Voice,
+    render_start: f64,
+    render_end: f64,
+}
+
+// 発音開始/終了のタイムライン上で、5ボイスへのアサインとボイススティールを解決する
+struct VoiceAllocator;
+
+impl VoiceAllocator {
+    // チャンネル種別とピッチに応じてボイスを選ぶ。戻り値のボイスがoccupants内に既に
+    // 埋まっていれば、呼び出し側がその占有ノートをスティールする。
+    fn choose_voice(note: &Note, occupants: &HashMap<Voice, usize>, notes: &[Note]) -> Voice {
+        if note.channel == DRUM_CHANNEL {
+            return if is_bass_drum_key(note.midi_note) {
+                Voice::Dmc
+            } else {
+                Voice::Noise
+            };
+        }
+
+        // Triangleが鳴っていればそのノートより低い音、空いていればPulseで鳴っている旋律ノート以下の音を
+        // 最低音とみなし、ベースとしてTriangleを引き継ぐ。
+        // 単独の最初の1音はPulseへ（鳴っている他の旋律がまだ無ければ「最低音」とは扱わない）
+        let existing_pulse_pitches: Vec<u8> = [Voice::Pulse1, Voice::Pulse2]
+            .iter()
+            .filter_map(|voice| occupants.get(voice).map(|&index| notes[index].midi_note))
+            .collect();
+        let is_lowest = match occupants.get(&Voice::Triangle) {
+            Some(&triangle_index) => note.midi_note < notes[triangle_index].midi_note,
+            None => {
+                !existing_pulse_pitches.is_empty()
+                    && existing_pulse_pitches
+                        .iter()
+                        .all(|&pitch| note.midi_note <= pitch)
+            }
+        };
+        if is_lowest {
+            return Voice::Triangle;
+        }
+
+        if !occupants.contains_key(&Voice::Pulse1) {
+            return Voice::Pulse1;
+        }
+        if !occupants.contains_key(&Voice::Pulse2) {
+            return Voice::Pulse2;
+        }
+
+        // 両方埋まっている場合は、最も古い（同着なら最も弱い）ノートを奪う
+        let pulse1 = &notes[occupants[&Voice::Pulse1]];
+        let pulse2 = &notes[occupants[&Voice::Pulse2]];
+        let steal_pulse1 = if pulse1.start_time != pulse2.start_time {
+            pulse1.start_time < pulse2.start_time
+        } else {
+            pulse1.velocity <= pulse2.velocity
+        };
+        if steal_pulse1 {
+            Voice::Pulse1
+        } else {
+            Voice::Pulse2
+        }
+    }
+
+    // 時間順にNoteOn/NoteOffを処理し、各ノートがどのボイスでいつからいつまで
+    // 鳴らされるかを確定する。スティールされたノートはそこで打ち切られる。
+    fn allocate(notes: &[Note]) -> Vec<VoiceAssignment> {
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+        enum EventKind {
+            End,
+            Start,
+        }
+
+        let mut events: Vec<(f64, EventKind, usize)> = Vec::new();
+        for (index, note) in notes.iter().enumerate() {
+            events.push((note.start_time, EventKind::Start, index));
+            events.push((note.start_time + note.duration, EventKind::End, index));
+        }
+        // 同時刻ではEndを先に処理し、切れたボイスをすぐ再利用できるようにする
+        events.sort_by(|a, b| {
+            a.0.partial_cmp(&b.0)
+                .unwrap()
+                .then(a.1.cmp(&b.1))
+        });
+
+        let mut occupants: HashMap<Voice, usize> = HashMap::new();
+        let mut open: HashMap<usize, (Voice, f64)> = HashMap::new();
+        let mut assignments = Vec::new();
+
+        for (time, kind, note_index) in events {
+            match kind {
+                EventKind::End => {
+                    if let Some((voice, render_start)) = open.remove(&note_index) {
+                        if occupants.get(&voice) == Some(&note_index) {
+                            occupants.remove(&voice);
+                        }
+                        assignments.push(VoiceAssignment {
+                            note_index,
+                            voice,
+                            render_start,
+                            render_end: time,
+                        });
+                    }
+                }
+                EventKind::Start => {
+                    let voice = Self::choose_voice(&notes[note_index], &occupants, notes);
+                    if let Some(&stolen_index) = occupants.get(&voice) {
+                        if let Some((_, render_start)) = open.remove(&stolen_index) {
+                            assignments.push(VoiceAssignment {
+                                note_index: stolen_index,
+                                voice,
+                                render_start,
+                                render_end: time,
+                            });
+                        }
+                    }
+                    occupants.insert(voice, note_index);
+                    open.insert(note_index, (voice, time));
+                }
+            }
+        }
+
+        // 末尾まで鳴っていたノートを確定する
+        for (note_index, (voice, render_start)) in open {
+            let note = &notes[note_index];
+            assignments.push(VoiceAssignment {
+                note_index,
+                voice,
+                render_start,
+                render_end: note.start_time + note.duration,
+            });
+        }
+
+        assignments
+    }
+}
+
+// 音符を波形に変換。voiceとgate_duration（実際に鳴っていた秒数。スティールされると
+// note.durationより短くなる）はVoiceAllocatorの割り当て結果から渡される。
+fn note_to_waveform(
+    note: &Note,
+    voice: Voice,
+    gate_duration: f64,
+    sample_rate: u32,
+    total_duration: f64,
+    channel_bends: &[(f64, u16)],
+    bend_range_semitones: f64,
+) -> Vec<f64> {
     let frequency = midi_note_to_frequency(note.midi_note);
     let volume = note.velocity as f64 / 127.0 * 0.7;
     let start_sample = (note.start_time * sample_rate as f64) as usize;
-    let note_samples = (note.duration * sample_rate as f64) as usize;
     let total_samples = (total_duration * sample_rate as f64) as usize;
 
-    let waveform = match note.channel % 4 {
-        0 => generate_square_wave(frequency, DutyCycle::Duty50, sample_rate, note.duration),
-        1 => generate_square_wave(frequency, DutyCycle::Duty25, sample_rate, note.duration),
-        2 => generate_triangle_wave(frequency, sample_rate, note.duration),
-        _ => generate_noise(false, sample_rate, note.duration),
+    let envelope = voice.envelope();
+    // ゲートが閉じた後もリリース分は発音を延長する
+    let render_duration = gate_duration + envelope.release;
+
+    let waveform = match voice {
+        Voice::Pulse1 => generate_pitched_wave(
+            frequency,
+            note.start_time,
+            channel_bends,
+            bend_range_semitones,
+            sample_rate,
+            render_duration,
+            |phase| if phase < DutyCycle::Duty50.value() { 1.0 } else { -1.0 },
+        ),
+        Voice::Pulse2 => generate_pitched_wave(
+            frequency,
+            note.start_time,
+            channel_bends,
+            bend_range_semitones,
+            sample_rate,
+            render_duration,
+            |phase| if phase < DutyCycle::Duty25.value() { 1.0 } else { -1.0 },
+        ),
+        Voice::Triangle => generate_pitched_wave(
+            frequency,
+            note.start_time,
+            channel_bends,
+            bend_range_semitones,
+            sample_rate,
+            render_duration,
+            |phase| {
+                if phase < 0.5 {
+                    4.0 * phase - 1.0
+                } else {
+                    3.0 - 4.0 * phase
+                }
+            },
+        ),
+        Voice::Noise => generate_noise(false, sample_rate, render_duration),
+        // DMCはサンプル再生だが、実サンプルデータを持たないため短周期ノイズで近似する
+        Voice::Dmc => generate_noise(true, sample_rate, render_duration),
     };
 
     let mut result = vec![0.0; total_samples];
-    let end_sample = std::cmp::min(start_sample + note_samples, total_samples);
+    let end_sample = std::cmp::min(start_sample + waveform.len(), total_samples);
 
-    for i in start_sample..end_sample {
-        if i - start_sample < waveform.len() {
-            result[i] = waveform[i - start_sample] * volume;
-        }
+    for (sample_index, slot) in result[start_sample..end_sample].iter_mut().enumerate() {
+        let t = sample_index as f64 / sample_rate as f64;
+        let amplitude = envelope.amplitude_at(t, gate_duration);
+        *slot = (waveform[sample_index] * volume * amplitude).clamp(-1.0, 1.0);
     }
 
     result
@@ -492,17 +1211,31 @@ fn create_wave_header(
     }
 }
 
-// WAVファイル書き込み
+// 左右の16ビットサンプル列をインターリーブする（L,R,L,R,...）
+fn interleave_stereo(left: &[i16], right: &[i16]) -> Vec<i16> {
+    let len = left.len().max(right.len());
+    let mut result = Vec::with_capacity(len * 2);
+
+    for i in 0..len {
+        result.push(*left.get(i).unwrap_or(&0));
+        result.push(*right.get(i).unwrap_or(&0));
+    }
+
+    result
+}
+
+// WAVファイル書き込み（ステレオ）
 fn write_wave_file<P: AsRef<Path>>(
     filename: P,
-    samples: &[f64],
+    left: &[f64],
+    right: &[f64],
     sample_rate: u32,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut file = File::create(filename)?;
 
-    let sample_data = convert_to_16bit(samples);
+    let sample_data = interleave_stereo(&convert_to_16bit(left), &convert_to_16bit(right));
     let data_size = (sample_data.len() * 2) as u32;
-    let header = create_wave_header(sample_rate, 1, 16, data_size);
+    let header = create_wave_header(sample_rate, 2, 16, data_size);
 
     // ヘッダー書き込み
     file.write_all(&header.chunk_id)?;
@@ -539,7 +1272,38 @@ fn convert_midi_to_wav<P1: AsRef<Path>, P2: AsRef<Path>>(
         midi_file.format, midi_file.track_count, midi_file.ticks_per_quarter
     );
 
-    let notes = events_to_notes(&midi_file, tempo);
+    render_midi_file(&midi_file, wav_filename, tempo)
+}
+
+// MUSファイルをWAVに変換するメイン関数。MUSは140Hz固定のクロックで駆動されるため、
+// ticks_per_quarter=140かつ1拍=1秒（60BPM相当）として読み込んだMidiFileをそのまま描画する。
+fn convert_mus_to_wav<P1: AsRef<Path>, P2: AsRef<Path>>(
+    mus_filename: P1,
+    wav_filename: P2,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let midi_file = read_mus_file(mus_filename)?;
+    println!(
+        "MUSファイルを読み込みました: {} tracks, {} ticks/quarter",
+        midi_file.track_count, midi_file.ticks_per_quarter
+    );
+
+    render_midi_file(&midi_file, wav_filename, 60.0)
+}
+
+// ミックス済みのステレオ波形。ファイル書き出し（render_midi_file）とライブ再生（play_midi_file）の
+// 両方がgenerate_stereo_buffersを土台にして、この共有レンダリングコアを再利用する。
+struct StereoBuffers {
+    left: Vec<f64>,
+    right: Vec<f64>,
+    sample_rate: u32,
+}
+
+// MidiFileを音符に変換し、5ボイスへ割り当ててステレオのミックス済み波形を生成する共通処理
+fn generate_stereo_buffers(
+    midi_file: &MidiFile,
+    tempo: f64,
+) -> Result<StereoBuffers, Box<dyn std::error::Error>> {
+    let (notes, pitch_bends, pans) = events_to_notes(midi_file, tempo);
     println!("{}個の音符を検出しました", notes.len());
 
     if notes.is_empty() {
@@ -553,22 +1317,210 @@ fn convert_midi_to_wav<P1: AsRef<Path>, P2: AsRef<Path>>(
         + 1.0; // 余裕を持たせる
 
     let sample_rate = 44100;
+    let empty_bends = Vec::new();
+    let total_samples = (sample_rate as f64 * total_duration) as usize;
 
     println!("総演奏時間: {:.2}秒", total_duration);
+    println!("5ボイスへの割り当てを計算中...");
+
+    let assignments = VoiceAllocator::allocate(&notes);
+
     println!("波形を生成中...");
 
-    let waveforms: Vec<Vec<f64>> = notes
-        .iter()
-        .map(|note| note_to_waveform(note, sample_rate, total_duration))
+    let mut left_voice_buffers: HashMap<Voice, Vec<f64>> = Voice::all()
+        .into_iter()
+        .map(|voice| (voice, vec![0.0; total_samples]))
+        .collect();
+    let mut right_voice_buffers: HashMap<Voice, Vec<f64>> = Voice::all()
+        .into_iter()
+        .map(|voice| (voice, vec![0.0; total_samples]))
         .collect();
 
-    let mixed = mix_waveforms(waveforms);
-    write_wave_file(wav_filename, &mixed, sample_rate)?;
+    for assignment in &assignments {
+        let note = &notes[assignment.note_index];
+        let channel_bends = pitch_bends.get(&note.channel).unwrap_or(&empty_bends);
+        let gate_duration = assignment.render_end - assignment.render_start;
+        let rendered = note_to_waveform(
+            note,
+            assignment.voice,
+            gate_duration,
+            sample_rate,
+            total_duration,
+            channel_bends,
+            DEFAULT_BEND_RANGE_SEMITONES,
+        );
+
+        let pan = effective_pan(assignment.voice, note.channel, &pans);
+        let left_buffer = left_voice_buffers.get_mut(&assignment.voice).unwrap();
+        let right_buffer = right_voice_buffers.get_mut(&assignment.voice).unwrap();
+        for (i, sample) in rendered.iter().enumerate() {
+            left_buffer[i] += sample * (1.0 - pan);
+            right_buffer[i] += sample * pan;
+        }
+    }
+
+    let left_waveforms: Vec<Vec<f64>> = Voice::all()
+        .into_iter()
+        .map(|voice| left_voice_buffers.remove(&voice).unwrap())
+        .collect();
+    let right_waveforms: Vec<Vec<f64>> = Voice::all()
+        .into_iter()
+        .map(|voice| right_voice_buffers.remove(&voice).unwrap())
+        .collect();
+
+    let left = mix_waveforms(left_waveforms);
+    let right = mix_waveforms(right_waveforms);
+
+    Ok(StereoBuffers {
+        left,
+        right,
+        sample_rate,
+    })
+}
+
+// MidiFileを音符に変換し、5ボイスへ割り当ててWAVとして書き出す
+fn render_midi_file<P: AsRef<Path>>(
+    midi_file: &MidiFile,
+    wav_filename: P,
+    tempo: f64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let buffers = generate_stereo_buffers(midi_file, tempo)?;
+    write_wave_file(wav_filename, &buffers.left, &buffers.right, buffers.sample_rate)?;
 
     println!("WAVファイルを生成しました！");
     Ok(())
 }
 
+// MidiFileを音符に変換し、ファイルに書き出さずデフォルトの出力デバイスへ直接再生する
+fn play_midi_file(midi_file: &MidiFile, tempo: f64) -> Result<(), Box<dyn std::error::Error>> {
+    let buffers = generate_stereo_buffers(midi_file, tempo)?;
+
+    println!("再生中... (Ctrl+Cで中断)");
+    play_stereo_buffers(&buffers.left, &buffers.right, buffers.sample_rate)?;
+    println!("再生が終了しました");
+    Ok(())
+}
+
+// 左右のサンプル列をcpal経由でデフォルト出力デバイスにストリーミング再生する。
+// データコールバックはリアルタイム制約下で動くため、ロックを取らずAtomicな再生位置だけを共有する。
+fn play_stereo_buffers(
+    left: &[f64],
+    right: &[f64],
+    sample_rate: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let interleaved: Arc<Vec<f32>> = Arc::new(
+        interleave_stereo(&convert_to_16bit(left), &convert_to_16bit(right))
+            .into_iter()
+            .map(|sample| sample as f32 / 32767.0)
+            .collect(),
+    );
+
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or("再生可能な出力デバイスが見つかりません")?;
+
+    // ハードコードのf32/44100Hzではなく、デバイスが実際にサポートするフォーマット/レートを使う
+    let supported_config = device.default_output_config()?;
+    let sample_format = supported_config.sample_format();
+    let config: cpal::StreamConfig = supported_config.into();
+
+    if config.sample_rate.0 != sample_rate {
+        println!(
+            "注意: レンダリングしたサンプルレート({} Hz)と出力デバイスのレート({} Hz)が異なります",
+            sample_rate, config.sample_rate.0
+        );
+    }
+
+    let position = Arc::new(AtomicUsize::new(0));
+    let finished = Arc::new(AtomicBool::new(false));
+
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => build_output_stream::<f32>(
+            &device,
+            &config,
+            interleaved.clone(),
+            position.clone(),
+            finished.clone(),
+        )?,
+        cpal::SampleFormat::I16 => build_output_stream::<i16>(
+            &device,
+            &config,
+            interleaved.clone(),
+            position.clone(),
+            finished.clone(),
+        )?,
+        cpal::SampleFormat::U16 => build_output_stream::<u16>(
+            &device,
+            &config,
+            interleaved.clone(),
+            position.clone(),
+            finished.clone(),
+        )?,
+        other => return Err(format!("サポートされていないサンプルフォーマットです: {:?}", other).into()),
+    };
+
+    stream.play()?;
+
+    while !finished.load(Ordering::Relaxed) {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    // デバイス内部のバッファに残ったサンプルが鳴り終わるまで少し待つ
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    Ok(())
+}
+
+// サンプルフォーマットTに対応する出力ストリームを構築する。ステレオのソースサンプル列を
+// デバイスの実チャンネル数ぶんのフレームへ展開する（3ch目以降は無音、モノラルはLにフォールバック）。
+fn build_output_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    source: Arc<Vec<f32>>,
+    position: Arc<AtomicUsize>,
+    finished: Arc<AtomicBool>,
+) -> Result<cpal::Stream, Box<dyn std::error::Error>>
+where
+    T: cpal::Sample + cpal::FromSample<f32> + Send + 'static,
+{
+    let channels = config.channels as usize;
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut frame_index = position.load(Ordering::Relaxed);
+            for frame in data.chunks_mut(channels) {
+                let (left, right) = match (
+                    source.get(frame_index * 2),
+                    source.get(frame_index * 2 + 1),
+                ) {
+                    (Some(&l), Some(&r)) => {
+                        frame_index += 1;
+                        (l, r)
+                    }
+                    _ => {
+                        finished.store(true, Ordering::Relaxed);
+                        (0.0, 0.0)
+                    }
+                };
+                for (channel_index, sample) in frame.iter_mut().enumerate() {
+                    let value = match channel_index {
+                        0 => left,
+                        1 => right,
+                        _ => 0.0,
+                    };
+                    *sample = T::from_sample(value);
+                }
+            }
+            position.store(frame_index, Ordering::Relaxed);
+        },
+        |err| eprintln!("再生ストリームでエラーが発生しました: {}", err),
+        None,
+    )?;
+
+    Ok(stream)
+}
+
 // デモ用のサンプル生成
 fn generate_demo_nes_music() -> Result<(), Box<dyn std::error::Error>> {
     let sample_rate = 44100;
@@ -596,39 +1548,91 @@ fn generate_demo_nes_music() -> Result<(), Box<dyn std::error::Error>> {
     let mixed = mix_waveforms(vec![square1, square2, triangle]);
 
     // WAVファイルに出力
-    write_wave_file("demo_nes_sound.wav", &mixed, sample_rate)?;
+    write_wave_file("demo_nes_sound.wav", &mixed, &mixed, sample_rate)?;
     println!("デモNES音源のWAVファイル 'demo_nes_sound.wav' を生成しました！");
 
     Ok(())
 }
 
+// 拡張子が".mus"か、ファイル先頭が"MUS\x1a"マジックバイトであればMUSファイルとみなす
+fn is_mus_file<P: AsRef<Path>>(filename: P) -> bool {
+    let path = filename.as_ref();
+    if path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("mus"))
+        .unwrap_or(false)
+    {
+        return true;
+    }
+
+    let mut header = [0u8; 4];
+    match File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => &header == b"MUS\x1a",
+        Err(_) => false,
+    }
+}
+
+// 入力ファイルを読み込み、WAVには書き出さずその場で再生する（--playモード）
+fn play_input_file<P: AsRef<Path>>(input_file: P, tempo: f64) -> Result<(), Box<dyn std::error::Error>> {
+    let input_file = input_file.as_ref();
+    let midi_file = if is_mus_file(input_file) {
+        let midi_file = read_mus_file(input_file)?;
+        println!(
+            "MUSファイルを読み込みました: {} tracks, {} ticks/quarter",
+            midi_file.track_count, midi_file.ticks_per_quarter
+        );
+        play_midi_file(&midi_file, 60.0)?;
+        return Ok(());
+    } else {
+        read_midi_file(input_file)?
+    };
+    println!(
+        "MIDIファイルを読み込みました: Format {}, {} tracks, {} ticks/quarter",
+        midi_file.format, midi_file.track_count, midi_file.ticks_per_quarter
+    );
+    play_midi_file(&midi_file, tempo)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
+    let all_args: Vec<String> = std::env::args().collect();
+    let play_mode = all_args.iter().any(|arg| arg == "--play");
+    let args: Vec<&String> = all_args
+        .iter()
+        .skip(1)
+        .filter(|arg| arg.as_str() != "--play")
+        .collect();
 
-    if args.len() >= 2 {
-        let midi_file = &args[1];
-        let output_file = if args.len() >= 3 {
-            args[2].clone()
-        } else {
-            Path::new(midi_file)
-                .with_extension("wav")
-                .to_string_lossy()
-                .to_string()
-        };
-        let tempo = if args.len() >= 4 {
-            args[3].parse()?
-        } else {
-            120.0
-        };
+    if !args.is_empty() {
+        let input_file = args[0];
 
-        if Path::new(midi_file).exists() {
-            convert_midi_to_wav(midi_file, output_file, tempo)?;
-        } else {
-            eprintln!("MIDIファイル '{}' が見つかりません", midi_file);
+        if !Path::new(input_file).exists() {
+            eprintln!("入力ファイル '{}' が見つかりません", input_file);
             std::process::exit(1);
         }
+
+        if play_mode {
+            let tempo = if args.len() >= 2 { args[1].parse()? } else { 120.0 };
+            play_input_file(input_file, tempo)?;
+        } else {
+            let output_file = if args.len() >= 2 {
+                args[1].clone()
+            } else {
+                Path::new(input_file)
+                    .with_extension("wav")
+                    .to_string_lossy()
+                    .to_string()
+            };
+            let tempo = if args.len() >= 3 { args[2].parse()? } else { 120.0 };
+
+            if is_mus_file(input_file) {
+                convert_mus_to_wav(input_file, output_file)?;
+            } else {
+                convert_midi_to_wav(input_file, output_file, tempo)?;
+            }
+        }
     } else {
-        println!("使用方法: cargo run <MIDIファイル> [出力WAVファイル] [テンポ]");
+        println!("使用方法: cargo run <MIDI/MUSファイル> [出力WAVファイル] [テンポ]");
+        println!("          cargo run -- --play <MIDI/MUSファイル> [テンポ]");
         println!("デモファイルを生成します...");
         generate_demo_nes_music()?;
     }
@@ -671,4 +1675,198 @@ mod tests {
         assert!(!noise.is_empty());
         assert_eq!(noise.len(), 4410);
     }
+
+    #[test]
+    fn test_envelope_amplitude_shape() {
+        let envelope = Envelope {
+            attack: 0.1,
+            decay: 0.1,
+            sustain: 0.5,
+            release: 0.2,
+        };
+
+        assert!((envelope.amplitude_at(0.0, 1.0) - 0.0).abs() < 0.0001);
+        assert!((envelope.amplitude_at(0.1, 1.0) - 1.0).abs() < 0.0001);
+        assert!((envelope.amplitude_at(0.2, 1.0) - 0.5).abs() < 0.0001);
+        assert!((envelope.amplitude_at(0.5, 1.0) - 0.5).abs() < 0.0001);
+        assert!((envelope.amplitude_at(1.0, 1.0) - 0.5).abs() < 0.0001);
+        assert!((envelope.amplitude_at(1.2, 1.0) - 0.0).abs() < 0.0001);
+        assert!((envelope.amplitude_at(1.3, 1.0) - 0.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_envelope_release_starts_from_actual_level_on_short_gate() {
+        // noise_defaultと同じ値。gate_duration(0.01)がattack+decay(0.021)より短く、
+        // decayの途中でゲートが閉じるケース
+        let envelope = Envelope {
+            attack: 0.001,
+            decay: 0.02,
+            sustain: 0.6,
+            release: 0.03,
+        };
+
+        let just_before_gate_close = envelope.amplitude_at(0.0099, 0.01);
+        let just_after_gate_close = envelope.amplitude_at(0.0101, 0.01);
+        // decayが完了していない時点で閉じても、リリースはsustainからではなく
+        // その瞬間の実際の振幅から滑らかに始まる（1サンプルでの急落が無い）
+        assert!((just_before_gate_close - just_after_gate_close).abs() < 0.01);
+    }
+
+    fn make_note(channel: u8, midi_note: u8, start_time: f64, duration: f64) -> Note {
+        Note {
+            midi_note,
+            channel,
+            start_time,
+            duration,
+            velocity: 100,
+        }
+    }
+
+    #[test]
+    fn test_voice_allocator_routes_drums_and_lowest_melody() {
+        let notes = vec![
+            make_note(0, 72, 0.0, 1.0), // メロディ（高音）
+            make_note(0, 48, 0.0, 1.0), // メロディ（低音） -> Triangle
+            make_note(DRUM_CHANNEL, 36, 0.0, 0.2), // バスドラム -> Dmc
+            make_note(DRUM_CHANNEL, 42, 0.0, 0.2), // クローズハイハット -> Noise
+        ];
+
+        let assignments = VoiceAllocator::allocate(&notes);
+        let voice_of = |index: usize| {
+            assignments
+                .iter()
+                .find(|a| a.note_index == index)
+                .map(|a| a.voice)
+        };
+
+        assert_eq!(voice_of(0), Some(Voice::Pulse1));
+        assert_eq!(voice_of(1), Some(Voice::Triangle));
+        assert_eq!(voice_of(2), Some(Voice::Dmc));
+        assert_eq!(voice_of(3), Some(Voice::Noise));
+    }
+
+    #[test]
+    fn test_voice_allocator_steals_oldest_pulse_when_third_note_arrives() {
+        let notes = vec![
+            make_note(0, 60, 0.0, 2.0), // Pulse1（最初に鳴る）
+            make_note(0, 62, 0.1, 2.0), // Pulse2
+            make_note(0, 64, 0.2, 2.0), // 両方埋まっているのでPulse1をスティール
+        ];
+
+        let assignments = VoiceAllocator::allocate(&notes);
+
+        let first = assignments.iter().find(|a| a.note_index == 0).unwrap();
+        assert_eq!(first.voice, Voice::Pulse1);
+        assert_eq!(first.render_end, 0.2); // スティールにより短縮される
+
+        let third = assignments.iter().find(|a| a.note_index == 2).unwrap();
+        assert_eq!(third.voice, Voice::Pulse1);
+    }
+
+    #[test]
+    fn test_effective_pan_uses_cc10_override_or_voice_default() {
+        let mut pans: PanMap = HashMap::new();
+        pans.insert(0, 127); // チャンネル0はCC10で全右に設定
+
+        assert!((effective_pan(Voice::Pulse1, 0, &pans) - 1.0).abs() < 0.0001);
+        // CC10が無いチャンネルはボイスごとの既定値にフォールバック
+        assert!((effective_pan(Voice::Pulse1, 1, &pans) - 0.35).abs() < 0.0001);
+        assert!((effective_pan(Voice::Pulse2, 1, &pans) - 0.65).abs() < 0.0001);
+        assert!((effective_pan(Voice::Triangle, 1, &pans) - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_read_mus_file_decodes_play_and_release_note() {
+        // ヘッダー16バイト（id, scoreLen=7, scoreStart=16, channels=1, secChannels=0, instrCnt=0, dummy=0）
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(b"MUS\x1a");
+        bytes.extend_from_slice(&7u16.to_le_bytes()); // scoreLength
+        bytes.extend_from_slice(&16u16.to_le_bytes()); // scoreStart
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // channels
+        bytes.extend_from_slice(&0u16.to_le_bytes()); // secondary channels
+        bytes.extend_from_slice(&0u16.to_le_bytes()); // instrument count
+        bytes.extend_from_slice(&0u16.to_le_bytes()); // dummy
+
+        // Play Note (channel0, note60, volume64, is_last) -> delay10
+        bytes.extend_from_slice(&[0x90, 0xBC, 0x40, 0x0A]);
+        // Release Note (channel0, note60)
+        bytes.extend_from_slice(&[0x00, 0x3C]);
+        // Score End
+        bytes.push(0x60);
+
+        let path = std::env::temp_dir().join("midi2chiptune_test.mus");
+        std::fs::write(&path, &bytes).unwrap();
+        let midi_file = read_mus_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(midi_file.ticks_per_quarter, 140);
+        assert_eq!(midi_file.tracks.len(), 1);
+        assert_eq!(
+            midi_file.tracks[0].events,
+            vec![
+                MidiEvent::NoteOn {
+                    channel: 0,
+                    note: 60,
+                    velocity: 64,
+                    time: 0,
+                },
+                MidiEvent::NoteOff {
+                    channel: 0,
+                    note: 60,
+                    time: 10,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pitch_bend_cents() {
+        // センターはベンドなし
+        assert!((pitch_bend_cents(8192, 2.0) - 0.0).abs() < 0.0001);
+        // フルアップは+bend_range_semitones半音=200セント
+        assert!((pitch_bend_cents(16383, 2.0) - 200.0).abs() < 0.1);
+        // フルダウンは-2半音=-200セント
+        assert!((pitch_bend_cents(0, 2.0) - (-200.0)).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_bend_value_at_uses_latest_event_at_or_before_time() {
+        let bends = vec![(1.0, 10000u16), (2.0, 4000u16)];
+        assert_eq!(bend_value_at(&bends, 0.0), 8192); // イベント前はセンター
+        assert_eq!(bend_value_at(&bends, 1.5), 10000);
+        assert_eq!(bend_value_at(&bends, 2.5), 4000);
+    }
+
+    #[test]
+    fn test_read_midi_header_rejects_zero_ticks_per_quarter() {
+        // divisionが0だと以降のtick->秒変換がNaNになるため、ヘッダーの時点で拒否する
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(b"MThd");
+        bytes.extend_from_slice(&6u32.to_be_bytes());
+        bytes.extend_from_slice(&0u16.to_be_bytes()); // format
+        bytes.extend_from_slice(&1u16.to_be_bytes()); // track_count
+        bytes.extend_from_slice(&0u16.to_be_bytes()); // ticks_per_quarter = 0
+
+        let mut pos = 0;
+        assert!(read_midi_header(&bytes, &mut pos).is_err());
+    }
+
+    #[test]
+    fn test_ticks_to_seconds_without_tempo_map() {
+        // テンポイベントが無い場合はCLIのtempo由来の一定値を使う（480tpq, 120BPM = 500000us/q）
+        let seconds = ticks_to_seconds_with_map(480, &[], 480, 500_000);
+        assert!((seconds - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_ticks_to_seconds_with_tempo_change() {
+        // 480tpqで、tick 480からテンポが120BPM→60BPMに変化
+        let tempo_map = vec![(480, 1_000_000)];
+        // 変化前（tick 240）は120BPMで0.25秒
+        let before = ticks_to_seconds_with_map(240, &tempo_map, 480, 500_000);
+        assert!((before - 0.25).abs() < 0.0001);
+        // 変化後（tick 960）は変化前0.5秒 + 60BPMでの1拍1秒 = 1.5秒
+        let after = ticks_to_seconds_with_map(960, &tempo_map, 480, 500_000);
+        assert!((after - 1.5).abs() < 0.0001);
+    }
 }